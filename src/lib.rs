@@ -1,27 +1,41 @@
 use std::{
-    char,
-    cmp::{Ordering, Reverse},
+    cmp::Reverse,
     collections::{BinaryHeap, HashMap},
     error::Error,
     fmt::Display,
     fs,
+    io::{self, Read, Write},
 };
 
+#[derive(Debug, PartialEq)]
+pub enum Mode {
+    Compress,
+    Decompress,
+}
+
 pub struct Config {
+    pub mode: Mode,
     pub input_file: String,
     pub output_file: String,
 }
 
 impl Config {
     pub fn build(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
+        if args.len() < 4 {
             return Err("Incorrect arguments supplied");
         }
 
-        let input_file = args[1].clone();
-        let output_file = args[2].clone();
+        let mode = match args[1].as_str() {
+            "compress" => Mode::Compress,
+            "decompress" => Mode::Decompress,
+            _ => return Err("mode must be one of: compress, decompress"),
+        };
+
+        let input_file = args[2].clone();
+        let output_file = args[3].clone();
 
         Ok(Config {
+            mode,
             input_file,
             output_file,
         })
@@ -29,118 +43,618 @@ impl Config {
 }
 
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.input_file)?;
-
-    let char_table = generate_char_table(contents);
+    match config.mode {
+        Mode::Compress => compress(config),
+        Mode::Decompress => decompress(config),
+    }
+}
 
-    // we are fine to consume the char_table here as
-    // its never needed again when we have converted
-    // the keys and values to HuffNode
-    let queue = build_priority_queue(char_table);
+fn compress(config: Config) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read(config.input_file)?;
+    let output = fs::File::create(config.output_file)?;
 
-    // TODO: Move the build huff tree to HuffTree::from_queue()
-    let huff_tree = build_huff_tree(queue);
+    let encoder = Encoder::train(contents.as_slice())?;
+    encoder.encode(contents.as_slice(), output)?;
 
-    println!("{huff_tree:#?}");
+    Ok(())
+}
 
-    let huff_table = HuffTable::from_huff_tree(huff_tree);
+fn decompress(config: Config) -> Result<(), Box<dyn Error>> {
+    let input = fs::File::open(config.input_file)?;
+    let output = fs::File::create(config.output_file)?;
 
-    println!("{huff_table:#?}");
+    Decoder::decode(input, output)?;
 
     Ok(())
 }
 
-fn generate_char_table(contents: String) -> HashMap<char, usize> {
-    contents.chars().fold(HashMap::new(), |mut acc, char| {
-        *acc.entry(char).or_insert(0) += 1;
+fn generate_byte_table(contents: &[u8]) -> HashMap<u8, usize> {
+    contents.iter().fold(HashMap::new(), |mut acc, &byte| {
+        *acc.entry(byte).or_insert(0) += 1;
         acc
     })
 }
 
-#[derive(Debug)]
-enum HuffNode {
-    Leaf {
-        element: char,
-        weight: usize,
-    },
-    Internal {
-        weight: usize,
-        left: Box<HuffNode>,
-        right: Box<HuffNode>,
-    },
+/// Packs Huffman codes MSB-first, writing each completed byte straight
+/// through to `writer` so encoding a stream never needs to buffer the
+/// whole packed output in memory.
+struct BitWriter<W: Write> {
+    writer: W,
+    current: u8,
+    filled: u8,
+}
+
+impl<W: Write> BitWriter<W> {
+    fn new(writer: W) -> Self {
+        Self {
+            writer,
+            current: 0,
+            filled: 0,
+        }
+    }
+
+    fn push_bits(&mut self, code: u128, bits: usize) -> io::Result<()> {
+        for i in (0..bits).rev() {
+            let bit = (code >> i) & 1;
+            self.current = (self.current << 1) | bit as u8;
+            self.filled += 1;
+
+            if self.filled == 8 {
+                self.writer.write_all(&[self.current])?;
+                self.current = 0;
+                self.filled = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pads the final partial byte with zero bits, writes it, and returns
+    /// the count of valid bits it held before padding.
+    fn finish(mut self) -> io::Result<u8> {
+        if self.filled == 0 {
+            return Ok(8);
+        }
+
+        let valid_bits = self.filled;
+        self.current <<= 8 - self.filled;
+        self.writer.write_all(&[self.current])?;
+
+        Ok(valid_bits)
+    }
+}
+
+/// A trained Huffman code table, reusable across many `encode` calls. The
+/// table is derived once from a frequency distribution, decoupling code
+/// assignment from any particular input or output.
+pub struct Encoder {
+    table: HuffTable,
+}
+
+impl Encoder {
+    /// Builds an encoder directly from symbol frequencies, for callers
+    /// that already know their input's distribution (e.g. a shared
+    /// dictionary reused across many small messages).
+    ///
+    /// Errors if `frequencies` is empty: a Huffman tree needs at least one
+    /// symbol to have a root, so there is no code table to build.
+    pub fn from_frequencies(frequencies: &HashMap<u8, usize>) -> io::Result<Self> {
+        if frequencies.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "cannot build a Huffman code table from empty input",
+            ));
+        }
+
+        let (arena, heap, len) = build_priority_queue(frequencies.clone());
+        let huff_tree = build_huff_tree(arena, heap, len);
+        let table = HuffTable::from_huff_tree(&huff_tree)?;
+
+        Ok(Self { table })
+    }
+
+    /// Trains an encoder by reading `reader` to the end and counting byte
+    /// frequencies.
+    pub fn train<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents)?;
+
+        Self::from_frequencies(&generate_byte_table(&contents))
+    }
+
+    /// Streams `reader` through this encoder's code map into a packed
+    /// bitstream written to `writer`: a header describing the code table,
+    /// then the packed codes, then a trailing byte giving the count of
+    /// valid bits in the final packed byte. The trailing count is written
+    /// last, not alongside the header, so the whole stream can be produced
+    /// in a single pass without buffering it.
+    pub fn encode<R: Read, W: Write>(&self, mut reader: R, mut writer: W) -> io::Result<()> {
+        let code_map = self.table.code_map();
+
+        writer.write_all(&self.table.serialize())?;
+
+        let mut bit_writer = BitWriter::new(&mut writer);
+        let mut buf = [0u8; 8192];
+        loop {
+            let read = reader.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+
+            for &byte in &buf[..read] {
+                let &(code, bits) = code_map.get(&byte).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "input contains a byte not in the encoder's trained symbol set",
+                    )
+                })?;
+                bit_writer.push_bits(code, bits)?;
+            }
+        }
+        let valid_bits_in_last_byte = bit_writer.finish()?;
+
+        writer.write_all(&[valid_bits_in_last_byte])?;
+
+        Ok(())
+    }
+}
+
+/// Reverses the framing `Encoder::encode` writes: a code-table header,
+/// the packed bitstream, and a trailing valid-bit count.
+pub struct Decoder;
+
+impl Decoder {
+    /// Parses a complete encoded stream into its code table and packed
+    /// body, shared by both decode paths below.
+    fn parse_stream(contents: &[u8]) -> io::Result<(HuffTable, &[u8], u8)> {
+        let (huff_table, header_len) = HuffTable::deserialize(contents)?;
+        let valid_bits_in_last_byte = *contents
+            .last()
+            .expect("stream has a trailing valid-bit count");
+        let packed = &contents[header_len..contents.len() - 1];
+
+        Ok((huff_table, packed, valid_bits_in_last_byte))
+    }
+
+    /// Decodes by walking the Huffman tree one bit at a time.
+    pub fn decode<R: Read, W: Write>(mut reader: R, mut writer: W) -> io::Result<()> {
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents)?;
+
+        let (huff_table, packed, valid_bits_in_last_byte) = Self::parse_stream(&contents)?;
+        let huff_tree = HuffTree::from_canonical_table(&huff_table);
+        writer.write_all(&huff_tree.decode(packed, valid_bits_in_last_byte))?;
+
+        Ok(())
+    }
+
+    /// Decodes using `TableDecoder`'s two-level lookup tables instead of a
+    /// bit-at-a-time tree walk: slower to set up, but most codes resolve
+    /// in a single array lookup, trading build cost for throughput.
+    pub fn decode_fast<R: Read, W: Write>(mut reader: R, mut writer: W) -> io::Result<()> {
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents)?;
+
+        let (huff_table, packed, valid_bits_in_last_byte) = Self::parse_stream(&contents)?;
+        let table_decoder = TableDecoder::from_huff_table(&huff_table);
+        writer.write_all(&table_decoder.decode(packed, valid_bits_in_last_byte))?;
+
+        Ok(())
+    }
+}
+
+/// Every symbol the tree can hold: the full `u8` range.
+const SYMBOL_COUNT: usize = u8::MAX as usize + 1;
+/// Every symbol plus every internal node a Huffman tree over that many
+/// leaves can ever need.
+const ARENA_SIZE: usize = 2 * SYMBOL_COUNT - 1;
+
+/// A single slot in the tree's arena. Leaves carry `symbol`; internal
+/// nodes carry `left`/`right`. Being `Copy` and index-linked rather than
+/// `Box`-linked means the whole tree lives in one fixed-size allocation.
+#[derive(Debug, Clone, Copy)]
+struct HuffNode {
+    count: usize,
+    parent: Option<usize>,
+    left: Option<usize>,
+    right: Option<usize>,
+    symbol: Option<u8>,
+}
+
+impl HuffNode {
+    const EMPTY: HuffNode = HuffNode {
+        count: 0,
+        parent: None,
+        left: None,
+        right: None,
+        symbol: None,
+    };
 }
 
 #[derive(Debug)]
 struct HuffTree {
-    root: Box<HuffNode>,
+    arena: [HuffNode; ARENA_SIZE],
+    root: usize,
 }
 
 impl HuffTree {
-    fn new_leaf(element: char, weight: usize) -> Self {
-        Self {
-            root: Box::new(HuffNode::Leaf { element, weight }),
+    #[cfg(test)]
+    fn weight(&self) -> usize {
+        self.arena[self.root].count
+    }
+
+    /// Rebuilds a decode tree from a canonical code table alone. Since
+    /// canonical codes are fully determined by each symbol's bit-length,
+    /// a decoder never needs the tree shape itself, only these
+    /// `(symbol, code, bits)` rows.
+    fn from_canonical_table(table: &HuffTable) -> Self {
+        let mut arena = [HuffNode::EMPTY; ARENA_SIZE];
+        let mut len = 1; // index 0 is reserved for the root
+
+        for row in &table.rows {
+            Self::insert(&mut arena, &mut len, 0, row.code, row.bits, row.symbol);
         }
+
+        Self { arena, root: 0 }
     }
 
-    fn new_internal(left: HuffNode, right: HuffNode) -> Self {
-        let left_weight = match left {
-            HuffNode::Internal { weight, .. } => weight,
-            HuffNode::Leaf { weight, .. } => weight,
-        };
+    fn insert(
+        arena: &mut [HuffNode; ARENA_SIZE],
+        len: &mut usize,
+        index: usize,
+        code: u128,
+        bits: usize,
+        symbol: u8,
+    ) {
+        if bits == 0 {
+            arena[index].symbol = Some(symbol);
+            return;
+        }
 
-        let right_weight = match right {
-            HuffNode::Internal { weight, .. } => weight,
-            HuffNode::Leaf { weight, .. } => weight,
+        let top_bit = (code >> (bits - 1)) & 1;
+        let existing = if top_bit == 0 {
+            arena[index].left
+        } else {
+            arena[index].right
         };
 
-        let total_weight = left_weight + right_weight;
+        let child_index = existing.unwrap_or_else(|| {
+            let new_index = *len;
+            *len += 1;
+
+            arena[new_index] = HuffNode {
+                parent: Some(index),
+                ..HuffNode::EMPTY
+            };
+
+            if top_bit == 0 {
+                arena[index].left = Some(new_index);
+            } else {
+                arena[index].right = Some(new_index);
+            }
+
+            new_index
+        });
+
+        Self::insert(arena, len, child_index, code, bits - 1, symbol);
+    }
+
+    /// Walks the packed bitstream one bit at a time, following `left` on a
+    /// `0` and `right` on a `1` until a leaf is reached, then restarting
+    /// from the root. Stops after `valid_bits_in_last_byte` bits of the
+    /// final byte so trailing zero padding is never decoded.
+    fn decode(&self, data: &[u8], valid_bits_in_last_byte: u8) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut index = self.root;
+
+        for (i, byte) in data.iter().enumerate() {
+            let bits_in_byte = if i == data.len() - 1 {
+                valid_bits_in_last_byte
+            } else {
+                8
+            };
+
+            for bit_index in 0..bits_in_byte {
+                let bit = (byte >> (7 - bit_index)) & 1;
+                let node = self.arena[index];
+
+                index = if bit == 0 {
+                    node.left.expect("internal node missing left child")
+                } else {
+                    node.right.expect("internal node missing right child")
+                };
+
+                if let Some(symbol) = self.arena[index].symbol {
+                    output.push(symbol);
+                    index = self.root;
+                }
+            }
+        }
+
+        output
+    }
+}
+
+/// Reads a packed bitstream MSB-first, honoring `valid_bits_in_last_byte`
+/// so the zero padding `BitWriter` added is never mistaken for data.
+struct BitReader<'a> {
+    data: &'a [u8],
+    total_bits: usize,
+    pos: usize,
+}
 
-        let (left, right) = if left_weight <= right_weight {
-            (left, right)
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8], valid_bits_in_last_byte: u8) -> Self {
+        let total_bits = if data.is_empty() {
+            0
         } else {
-            (right, left)
+            (data.len() - 1) * 8 + valid_bits_in_last_byte as usize
         };
 
         Self {
-            root: Box::new(HuffNode::Internal {
-                weight: total_weight,
-                left: Box::new(left),
-                right: Box::new(right),
-            }),
+            data,
+            total_bits,
+            pos: 0,
         }
     }
 
-    fn weight(&self) -> usize {
-        match *self.root {
-            HuffNode::Internal { weight, .. } => weight,
-            HuffNode::Leaf { weight, .. } => weight,
+    fn bit_at(&self, index: usize) -> u8 {
+        let byte = self.data[index / 8];
+        (byte >> (7 - (index % 8))) & 1
+    }
+
+    /// Peeks up to `count` bits from the current position without
+    /// consuming them, zero-padding past the end of the real stream.
+    /// Returns `None` once decoding has reached the end of the stream.
+    fn peek(&self, count: usize) -> Option<usize> {
+        if self.pos >= self.total_bits {
+            return None;
+        }
+
+        let mut value = 0usize;
+        for i in 0..count {
+            let index = self.pos + i;
+            let bit = if index < self.total_bits {
+                self.bit_at(index)
+            } else {
+                0
+            };
+            value = (value << 1) | bit as usize;
         }
+
+        Some(value)
     }
+
+    fn consume(&mut self, count: usize) {
+        self.pos += count;
+    }
+
+    fn next_bit(&mut self) -> Option<u8> {
+        if self.pos >= self.total_bits {
+            return None;
+        }
+
+        let bit = self.bit_at(self.pos);
+        self.pos += 1;
+        Some(bit)
+    }
+}
+
+/// Bits of direct lookup the main table resolves codes with. 10 works
+/// well in practice: big enough to resolve almost every real-world
+/// symbol in one lookup, small enough to build and hold cheaply.
+const MAIN_TABLE_BITS: usize = 10;
+const MAIN_TABLE_SIZE: usize = 1 << MAIN_TABLE_BITS;
+
+/// The secondary trie holds one two-slot (left/right) node per internal
+/// node of the longest-code tail, so it can never need more nodes than
+/// the primary arena's internal-node budget.
+const SECONDARY_TREE_SIZE: usize = 2 * SYMBOL_COUNT + 1;
+const SECONDARY_TREE_NONE: i16 = -1;
+
+fn pack_main_entry(symbol: u8, bits: usize) -> i16 {
+    symbol as i16 | ((bits as i16) << 8)
 }
 
-impl PartialEq for HuffTree {
-    fn eq(&self, other: &Self) -> bool {
-        self.weight() == other.weight()
+fn unpack_main_entry(entry: i16) -> (u8, usize) {
+    ((entry & 0xFF) as u8, ((entry >> 8) & 0xFF) as usize)
+}
+
+fn secondary_root_ref(node: usize) -> i16 {
+    -(node as i16) - 1
+}
+
+fn secondary_root_index(entry: i16) -> usize {
+    (-entry - 1) as usize
+}
+
+/// A miniz/zlib-style two-level decoder: a direct `main_table` lookup
+/// resolves any code of `MAIN_TABLE_BITS` or fewer bits in one step;
+/// longer codes fall back to walking `tree` bit-by-bit for their tail.
+/// Building these tables costs more up front than `HuffTree::decode`'s
+/// bit-at-a-time walk, but decoding trades that for far higher
+/// throughput, since most codes resolve with a single array lookup.
+struct TableDecoder {
+    main_table: Box<[i16; MAIN_TABLE_SIZE]>,
+    tree: Box<[i16; SECONDARY_TREE_SIZE]>,
+}
+
+impl TableDecoder {
+    fn from_huff_table(table: &HuffTable) -> Self {
+        let mut main_table = Box::new([0i16; MAIN_TABLE_SIZE]);
+        let mut tree = Box::new([SECONDARY_TREE_NONE; SECONDARY_TREE_SIZE]);
+        let mut next_node = 0usize;
+
+        for row in &table.rows {
+            if row.bits <= MAIN_TABLE_BITS {
+                let shift = MAIN_TABLE_BITS - row.bits;
+                let base = (row.code << shift) as usize;
+                let entry = pack_main_entry(row.symbol, row.bits);
+
+                for fill in 0..(1 << shift) {
+                    main_table[base + fill] = entry;
+                }
+            } else {
+                let overflow_bits = row.bits - MAIN_TABLE_BITS;
+                let prefix = (row.code >> overflow_bits) as usize;
+                let remaining_code = row.code & ((1 << overflow_bits) - 1);
+
+                let root = if main_table[prefix] < 0 {
+                    secondary_root_index(main_table[prefix])
+                } else {
+                    let new_root = next_node;
+                    next_node += 1;
+                    main_table[prefix] = secondary_root_ref(new_root);
+                    new_root
+                };
+
+                Self::tree_insert(&mut tree, &mut next_node, root, remaining_code, overflow_bits, row.symbol);
+            }
+        }
+
+        Self { main_table, tree }
     }
+
+    fn tree_insert(
+        tree: &mut [i16; SECONDARY_TREE_SIZE],
+        next_node: &mut usize,
+        node: usize,
+        code: u128,
+        bits: usize,
+        symbol: u8,
+    ) {
+        let top_bit = ((code >> (bits - 1)) & 1) as usize;
+        let slot = node * 2 + top_bit;
+
+        if bits == 1 {
+            tree[slot] = symbol as i16;
+            return;
+        }
+
+        if tree[slot] == SECONDARY_TREE_NONE {
+            let child = *next_node;
+            *next_node += 1;
+            tree[slot] = SYMBOL_COUNT as i16 + child as i16;
+        }
+
+        let child = (tree[slot] - SYMBOL_COUNT as i16) as usize;
+        Self::tree_insert(tree, next_node, child, code, bits - 1, symbol);
+    }
+
+    fn decode(&self, data: &[u8], valid_bits_in_last_byte: u8) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut reader = BitReader::new(data, valid_bits_in_last_byte);
+
+        while let Some(prefix) = reader.peek(MAIN_TABLE_BITS) {
+            let entry = self.main_table[prefix];
+
+            if entry >= 0 {
+                let (symbol, bits) = unpack_main_entry(entry);
+                output.push(symbol);
+                reader.consume(bits);
+            } else {
+                reader.consume(MAIN_TABLE_BITS);
+                let mut node = secondary_root_index(entry);
+
+                loop {
+                    let bit = reader
+                        .next_bit()
+                        .expect("well-formed stream has enough bits for its own codes");
+                    let value = self.tree[node * 2 + bit as usize];
+
+                    if value < SYMBOL_COUNT as i16 {
+                        output.push(value as u8);
+                        break;
+                    }
+
+                    node = (value - SYMBOL_COUNT as i16) as usize;
+                }
+            }
+        }
+
+        output
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct QueueEntry {
+    count: usize,
+    // Leaves sort before internal nodes of the same count, mirroring the
+    // original tree-of-trees tie-break.
+    is_internal: bool,
+    index: usize,
 }
 
-impl Eq for HuffTree {}
+fn build_priority_queue(
+    byte_table: HashMap<u8, usize>,
+) -> ([HuffNode; ARENA_SIZE], BinaryHeap<Reverse<QueueEntry>>, usize) {
+    let mut arena = [HuffNode::EMPTY; ARENA_SIZE];
+    let mut heap = BinaryHeap::new();
+    let mut len = 0;
 
-impl PartialOrd for HuffTree {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.weight().cmp(&other.weight()))
+    for (symbol, count) in byte_table {
+        arena[len] = HuffNode {
+            count,
+            symbol: Some(symbol),
+            ..HuffNode::EMPTY
+        };
+
+        // max-heap is largest first so we want
+        // to use min-heap to build the Huffman tree
+        heap.push(Reverse(QueueEntry {
+            count,
+            is_internal: false,
+            index: len,
+        }));
+
+        len += 1;
     }
+
+    (arena, heap, len)
 }
 
-impl Ord for HuffTree {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.weight()
-            .cmp(&other.weight())
-            .then_with(|| match (&*self.root, &*other.root) {
-                (HuffNode::Leaf { .. }, HuffNode::Internal { .. }) => Ordering::Less,
-                (HuffNode::Internal { .. }, HuffNode::Leaf { .. }) => Ordering::Greater,
-                _ => Ordering::Equal,
-            })
+fn build_huff_tree(
+    mut arena: [HuffNode; ARENA_SIZE],
+    mut heap: BinaryHeap<Reverse<QueueEntry>>,
+    mut len: usize,
+) -> HuffTree {
+    while heap.len() > 1 {
+        let Reverse(a) = heap.pop().expect("expect better error handling");
+        let Reverse(b) = heap.pop().expect("expect better error handling");
+
+        let (left, right) = if a.count <= b.count {
+            (a.index, b.index)
+        } else {
+            (b.index, a.index)
+        };
+
+        let combined_index = len;
+        let combined_count = a.count + b.count;
+
+        arena[left].parent = Some(combined_index);
+        arena[right].parent = Some(combined_index);
+
+        arena[combined_index] = HuffNode {
+            count: combined_count,
+            left: Some(left),
+            right: Some(right),
+            ..HuffNode::EMPTY
+        };
+
+        heap.push(Reverse(QueueEntry {
+            count: combined_count,
+            is_internal: true,
+            index: combined_index,
+        }));
+
+        len += 1;
+    }
+
+    let Reverse(root_entry) = heap.pop().expect("better error handling");
+
+    HuffTree {
+        arena,
+        root: root_entry.index,
     }
 }
 
@@ -154,31 +668,162 @@ impl HuffTable {
         Self { rows: Vec::new() }
     }
 
-    fn add_row(&mut self, char: char, frequency: usize, code: usize, bits: usize) {
+    fn add_row(&mut self, symbol: u8, frequency: usize, code: u128, bits: usize) {
         self.rows.push(HuffTableRow {
-            char,
+            symbol,
             frequency,
             code,
             bits,
         });
     }
 
-    fn from_huff_tree(huff_tree: Reverse<HuffTree>) -> Self {
+    fn from_huff_tree(huff_tree: &HuffTree) -> io::Result<Self> {
         let mut table = Self::new();
-        Self::traverse_tree(&huff_tree.0.root, 0, 0, &mut table);
-        table
+        Self::traverse_tree(huff_tree, huff_tree.root, 0, 0, &mut table);
+
+        // A single distinct symbol collapses the tree to one leaf that is
+        // also the root, which traverse_tree assigns a 0-bit code. Encoding
+        // would then emit nothing per occurrence and the whole payload
+        // would vanish, so every symbol needs at least one bit.
+        if table.rows.len() == 1 {
+            table.rows[0].bits = 1;
+        }
+
+        table.canonicalize()?;
+        Ok(table)
+    }
+
+    fn traverse_tree(huff_tree: &HuffTree, index: usize, code: u128, bits: usize, table: &mut Self) {
+        let node = huff_tree.arena[index];
+
+        match node.symbol {
+            Some(symbol) => table.add_row(symbol, node.count, code, bits),
+            None => {
+                if let Some(left) = node.left {
+                    Self::traverse_tree(huff_tree, left, code << 1, bits + 1, table);
+                }
+                if let Some(right) = node.right {
+                    Self::traverse_tree(huff_tree, right, (code << 1) | 1, bits + 1, table);
+                }
+            }
+        }
     }
 
-    fn traverse_tree(node: &HuffNode, code: usize, bits: usize, table: &mut Self) {
-        match node {
-            HuffNode::Leaf { element, weight } => {
-                table.add_row(*element, *weight, code, bits);
+    /// Reassigns every row's `code` canonically so a decoder can derive
+    /// identical codes from bit-lengths alone, without the tree shape
+    /// `traverse_tree` walked them from. Rows are ordered by
+    /// `(bits, symbol)`; the first symbol of a given length keeps the
+    /// running `code`, each later symbol of that length increments it by
+    /// one, and a length increase left-shifts `code` by the difference.
+    ///
+    /// `code` is widened to `u128` while it's built up, since a
+    /// sufficiently skewed frequency distribution can demand codes well
+    /// past 64 bits; the width checks on each shift and `checked_add` turn
+    /// the rare case where even that isn't enough into an error instead of
+    /// a silent wrap or a debug-mode panic.
+    fn canonicalize(&mut self) -> io::Result<()> {
+        self.rows
+            .sort_by(|a, b| a.bits.cmp(&b.bits).then(a.symbol.cmp(&b.symbol)));
+
+        let overflow = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "canonical Huffman code exceeds the maximum representable width",
+            )
+        };
+
+        let mut code = 0u128;
+        let mut prev_bits = 0usize;
+
+        for (i, row) in self.rows.iter_mut().enumerate() {
+            if row.bits > u128::BITS as usize {
+                return Err(overflow());
             }
-            HuffNode::Internal { left, right, .. } => {
-                Self::traverse_tree(left, code << 1, bits + 1, table);
-                Self::traverse_tree(right, (code << 1) | 1, bits + 1, table);
+            if i > 0 && row.bits != prev_bits {
+                let shift = (row.bits - prev_bits) as u32;
+                // `checked_shl` only rejects shifts of 128 or more, not set
+                // bits shifted out the top, so check for those directly.
+                if code.leading_zeros() < shift {
+                    return Err(overflow());
+                }
+                code <<= shift;
             }
+            row.code = code;
+            code = code.checked_add(1).ok_or_else(overflow)?;
+            prev_bits = row.bits;
         }
+
+        Ok(())
+    }
+
+    /// Flattens the table into a `symbol -> (code, bits)` lookup for fast
+    /// access while encoding.
+    fn code_map(&self) -> HashMap<u8, (u128, usize)> {
+        self.rows
+            .iter()
+            .map(|row| (row.symbol, (row.code, row.bits)))
+            .collect()
+    }
+
+    /// Serializes the symbol set and per-symbol code length — the only
+    /// information a decoder needs, since canonical codes are fully
+    /// determined by `(bits, symbol)` alone.
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(4 + self.rows.len() * 2);
+        buf.extend((self.rows.len() as u32).to_le_bytes());
+
+        for row in &self.rows {
+            buf.push(row.symbol);
+            buf.push(row.bits as u8);
+        }
+
+        buf
+    }
+
+    /// Rebuilds a table from the bytes `serialize` produced, returning it
+    /// along with the number of header bytes consumed so the caller can
+    /// find where the packed bitstream begins. Errors instead of
+    /// panicking when `bytes` is too short to hold the header it claims
+    /// to, since this is the first thing decoding an arbitrary (and
+    /// possibly corrupt or truncated) file does.
+    fn deserialize(bytes: &[u8]) -> io::Result<(Self, usize)> {
+        let truncated_header = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream is too short to hold a Huffman code-table header",
+            )
+        };
+
+        if bytes.len() < 4 {
+            return Err(truncated_header());
+        }
+
+        let symbol_count =
+            u32::from_le_bytes(bytes[0..4].try_into().expect("header has a length prefix"))
+                as usize;
+
+        if symbol_count
+            .checked_mul(2)
+            .and_then(|body_len| body_len.checked_add(4))
+            .is_none_or(|header_len| header_len > bytes.len())
+        {
+            return Err(truncated_header());
+        }
+
+        let mut pos = 4;
+        let mut table = Self::new();
+
+        for _ in 0..symbol_count {
+            let symbol = bytes[pos];
+            let bits = bytes[pos + 1] as usize;
+            pos += 2;
+
+            table.add_row(symbol, 0, 0, bits);
+        }
+
+        table.canonicalize()?;
+
+        Ok((table, pos))
     }
 }
 
@@ -188,7 +833,7 @@ impl Display for HuffTable {
             write!(
                 f,
                 "{}, {}, {}, {}",
-                value.char, value.frequency, value.code, value.bits
+                value.symbol, value.frequency, value.code, value.bits
             )
         });
         Ok(())
@@ -197,9 +842,9 @@ impl Display for HuffTable {
 
 #[derive(Debug)]
 struct HuffTableRow {
-    char: char,
+    symbol: u8,
     frequency: usize,
-    code: usize,
+    code: u128,
     bits: usize,
 }
 
@@ -208,101 +853,304 @@ impl Display for HuffTableRow {
         write!(
             f,
             "{}, {}, {}, {}",
-            self.char, self.frequency, self.code, self.bits
+            self.symbol, self.frequency, self.code, self.bits
         )
     }
 }
 
-fn build_priority_queue(char_table: HashMap<char, usize>) -> BinaryHeap<Reverse<HuffTree>> {
-    // the initial queue will be leaf nodes only however at some point this
-    // needs to handle internal nodes...
-    char_table
-        .iter()
-        .map(|(key, value)| {
-            // max-heap is largest first so we want
-            // to use min-heap to build the Huffman tree
-            Reverse(HuffTree::new_leaf(*key, *value))
-        })
-        .collect()
-}
-
-fn build_huff_tree(mut queue: BinaryHeap<Reverse<HuffTree>>) -> Reverse<HuffTree> {
-    while queue.len() > 1 {
-        let tmp1 = queue.pop().expect("expect better error handling");
-        let tmp2 = queue.pop().expect("expect better error handling");
-        let combined = HuffTree::new_internal(*tmp1.0.root, *tmp2.0.root);
-        queue.push(Reverse(combined));
-    }
-    queue.pop().expect("better error handling")
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
-    fn create_char_table() {
-        let content: String = "hello".to_string();
-        let table: HashMap<char, usize> = HashMap::from([('h', 1), ('e', 1), ('l', 2), ('o', 1)]);
+    fn create_byte_table() {
+        let content = b"hello".to_vec();
+        let table: HashMap<u8, usize> =
+            HashMap::from([(b'h', 1), (b'e', 1), (b'l', 2), (b'o', 1)]);
 
-        assert_eq!(table, generate_char_table(content))
+        assert_eq!(table, generate_byte_table(&content))
     }
 
     #[test]
     fn create_priority_queue() {
-        let content = generate_char_table("abbcccdddd".to_string());
+        let content = generate_byte_table(b"abbcccdddd");
 
         // order is not guaranteed when iterating over a hash map so
         // we need to ensure in this test that the smallest occurrence
         // does not happen twice
-        let mut queue = build_priority_queue(content);
+        let (arena, mut heap, _len) = build_priority_queue(content);
 
-        if let Some(value) = queue.pop() {
-            match *value.0.root {
-                HuffNode::Leaf { element, .. } => {
-                    assert_eq!('a', element)
-                }
-                // We never build a queue from internal nodes
-                // They are only ever pushed to the queue
-                HuffNode::Internal { .. } => {
-                    panic!("oh no we should not build a queue from internal nodes")
-                }
-            }
+        if let Some(Reverse(entry)) = heap.pop() {
+            assert!(!entry.is_internal);
+            assert_eq!(arena[entry.index].symbol, Some(b'a'));
         }
     }
 
     #[test]
     fn create_huff_tree() {
-        let content = generate_char_table("aaaaabbbccd\n".to_string());
-        let queue = build_priority_queue(content);
-        let huff_tree = build_huff_tree(queue);
+        let content = generate_byte_table(b"aaaaabbbccd\n");
+        let (arena, heap, len) = build_priority_queue(content);
+        let huff_tree = build_huff_tree(arena, heap, len);
 
-        assert_eq!(huff_tree.0.weight(), 12);
+        assert_eq!(huff_tree.weight(), 12);
     }
 
     #[test]
     fn create_huff_table() {
-        let content = generate_char_table("aaaaabbbccd\n".to_string());
-        let queue = build_priority_queue(content);
-        let huff_tree = build_huff_tree(queue);
-        let huff_table = HuffTable::from_huff_tree(huff_tree);
+        let content = generate_byte_table(b"aaaaabbbccd\n");
+        let (arena, heap, len) = build_priority_queue(content);
+        let huff_tree = build_huff_tree(arena, heap, len);
+        let huff_table = HuffTable::from_huff_tree(&huff_tree).unwrap();
 
+        // Canonical assignment orders symbols by (bits, symbol), so ties at
+        // the same length (here '\n' and 'd') are broken deterministically
+        // by byte value instead of depending on tree-build order.
         let expected_table = HuffTable {
             rows: Vec::from([
-                HuffTableRow { char: 'a', frequency: 5, code: 0, bits: 1 },
-                HuffTableRow { char: 'b', frequency: 3, code: 2, bits: 2 },
-                HuffTableRow { char: 'c', frequency: 2, code: 6, bits: 3 },
-                // Generation for nodes of equal length is not deterministic
+                HuffTableRow { symbol: b'a', frequency: 5, code: 0, bits: 1 },
+                HuffTableRow { symbol: b'b', frequency: 3, code: 2, bits: 2 },
+                HuffTableRow { symbol: b'c', frequency: 2, code: 6, bits: 3 },
+                HuffTableRow { symbol: b'\n', frequency: 1, code: 14, bits: 4 },
+                HuffTableRow { symbol: b'd', frequency: 1, code: 15, bits: 4 },
             ]),
         };
-        
+
         for i in 0..expected_table.rows.len() {
             let expected_row: &HuffTableRow = &expected_table.rows[i];
             let huff_row: &HuffTableRow = &huff_table.rows[i];
-            assert_eq!(expected_row.char, huff_row.char);
+            assert_eq!(expected_row.symbol, huff_row.symbol);
             assert_eq!(expected_row.frequency, huff_row.frequency);
             assert_eq!(expected_row.code, huff_row.code);
             assert_eq!(expected_row.bits, huff_row.bits);
         }
     }
+
+    /// Packs `contents` through `code_map` directly, bypassing the public
+    /// `Encoder` (which always prefixes a header), for tests that only
+    /// care about the raw bitstream.
+    fn pack(contents: &[u8], code_map: &HashMap<u8, (u128, usize)>) -> (Vec<u8>, u8) {
+        let mut buf = Vec::new();
+        let mut writer = BitWriter::new(&mut buf);
+
+        for &byte in contents {
+            let (code, bits) = code_map[&byte];
+            writer.push_bits(code, bits).unwrap();
+        }
+
+        let valid_bits = writer.finish().unwrap();
+        (buf, valid_bits)
+    }
+
+    #[test]
+    fn bitwriter_packs_bits_msb_first() {
+        let code_map: HashMap<u8, (u128, usize)> =
+            HashMap::from([(b'a', (0, 1)), (b'b', (2, 2))]);
+
+        // a a b b -> 0 0 10 10 -> 0010 10(00) padded = 0b00101000
+        let (packed, valid_bits) = pack(b"aabb", &code_map);
+
+        assert_eq!(packed, vec![0b0010_1000]);
+        assert_eq!(valid_bits, 6);
+    }
+
+    #[test]
+    fn bitwriter_full_last_byte_reports_eight_valid_bits() {
+        let code_map: HashMap<u8, (u128, usize)> = HashMap::from([(b'a', (0b1010_1010, 8))]);
+
+        let (packed, valid_bits) = pack(b"a", &code_map);
+
+        assert_eq!(packed, vec![0b1010_1010]);
+        assert_eq!(valid_bits, 8);
+    }
+
+    #[test]
+    fn serialize_then_deserialize_round_trips_code_lengths() {
+        let content = generate_byte_table(b"aaaaabbbccd\n");
+        let (arena, heap, len) = build_priority_queue(content);
+        let huff_tree = build_huff_tree(arena, heap, len);
+        let huff_table = HuffTable::from_huff_tree(&huff_tree).unwrap();
+
+        let header = huff_table.serialize();
+        let (rebuilt_table, consumed) = HuffTable::deserialize(&header).unwrap();
+
+        assert_eq!(consumed, header.len());
+
+        for (original, rebuilt) in huff_table.rows.iter().zip(rebuilt_table.rows.iter()) {
+            assert_eq!(original.symbol, rebuilt.symbol);
+            assert_eq!(original.code, rebuilt.code);
+            assert_eq!(original.bits, rebuilt.bits);
+        }
+    }
+
+    #[test]
+    fn deserialize_errors_instead_of_panicking_on_malformed_input() {
+        assert!(HuffTable::deserialize(b"").is_err());
+        assert!(HuffTable::deserialize(b"\x05\x00\x00").is_err());
+
+        let content = generate_byte_table(b"aaaaabbbccd\n");
+        let (arena, heap, len) = build_priority_queue(content);
+        let huff_tree = build_huff_tree(arena, heap, len);
+        let huff_table = HuffTable::from_huff_tree(&huff_tree).unwrap();
+        let header = huff_table.serialize();
+        assert!(HuffTable::deserialize(&header[..5]).is_err());
+
+        assert!(HuffTable::deserialize(&[0xFF; 50]).is_err());
+    }
+
+    #[test]
+    fn decode_reverses_encode_a_single_distinct_symbol() {
+        let original: &[u8] = b"aaaaaaaaaa";
+
+        let encoder = Encoder::train(original).unwrap();
+        let mut stream = Vec::new();
+        encoder.encode(original, &mut stream).unwrap();
+
+        let mut decoded = Vec::new();
+        Decoder::decode(stream.as_slice(), &mut decoded).unwrap();
+
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn decode_reverses_encode() {
+        let original: &[u8] = b"aaaaabbbccd\n";
+
+        let encoder = Encoder::train(original).unwrap();
+        let mut stream = Vec::new();
+        encoder.encode(original, &mut stream).unwrap();
+
+        let mut decoded = Vec::new();
+        Decoder::decode(stream.as_slice(), &mut decoded).unwrap();
+
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn encoder_from_frequencies_matches_encoder_train() {
+        let original: &[u8] = b"aaaaabbbccd\n";
+
+        let trained = Encoder::train(original).unwrap();
+        let from_freq = Encoder::from_frequencies(&generate_byte_table(original)).unwrap();
+
+        assert_eq!(trained.table.code_map(), from_freq.table.code_map());
+    }
+
+    #[test]
+    fn encoder_train_on_empty_input_errors_instead_of_panicking() {
+        let empty: &[u8] = b"";
+
+        assert!(Encoder::train(empty).is_err());
+    }
+
+    #[test]
+    fn encode_errors_instead_of_panicking_on_an_untrained_byte() {
+        let encoder = Encoder::train(b"aaabbbccc" as &[u8]).unwrap();
+        let mut output = Vec::new();
+
+        assert!(encoder.encode(b"abcz" as &[u8], &mut output).is_err());
+    }
+
+    #[test]
+    fn encoder_and_decoder_round_trip_a_non_seekable_stream() {
+        // An encoder trained on one sample can encode a different stream
+        // entirely, as long as that stream's symbols are all covered.
+        let dictionary: &[u8] = b"aaaaabbbccd\n";
+        let encoder = Encoder::train(dictionary).unwrap();
+
+        let original: &[u8] = b"abcd\n";
+        let mut stream = Vec::new();
+        encoder.encode(original, &mut stream).unwrap();
+
+        let mut decoded = Vec::new();
+        Decoder::decode(stream.as_slice(), &mut decoded).unwrap();
+
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn decode_fast_agrees_with_decode() {
+        let original: &[u8] = b"aaaaabbbccd\n";
+
+        let encoder = Encoder::train(original).unwrap();
+        let mut stream = Vec::new();
+        encoder.encode(original, &mut stream).unwrap();
+
+        let mut decoded = Vec::new();
+        Decoder::decode(stream.as_slice(), &mut decoded).unwrap();
+
+        let mut decoded_fast = Vec::new();
+        Decoder::decode_fast(stream.as_slice(), &mut decoded_fast).unwrap();
+
+        assert_eq!(decoded, original);
+        assert_eq!(decoded_fast, original);
+    }
+
+    #[test]
+    fn table_decoder_agrees_with_tree_walk_decode() {
+        let content = generate_byte_table(b"aaaaabbbccd\n");
+        let (arena, heap, len) = build_priority_queue(content);
+        let huff_tree = build_huff_tree(arena, heap, len);
+        let huff_table = HuffTable::from_huff_tree(&huff_tree).unwrap();
+        let code_map = huff_table.code_map();
+
+        let original: &[u8] = b"aaaaabbbccd\n";
+        let (packed, valid_bits) = pack(original, &code_map);
+
+        let table_decoder = TableDecoder::from_huff_table(&huff_table);
+        assert_eq!(table_decoder.decode(&packed, valid_bits), original);
+    }
+
+    #[test]
+    fn canonicalize_supports_code_lengths_past_64_bits() {
+        // Fibonacci-shaped frequencies over enough symbols force a code
+        // length well past 64 bits — the distribution that used to
+        // overflow canonicalize's 64-bit `code` accumulator and panic.
+        let mut frequencies = HashMap::new();
+        let (mut a, mut b) = (1u128, 1u128);
+        for symbol in 0u8..90 {
+            frequencies.insert(symbol, a as usize);
+            let next = a + b;
+            b = a;
+            a = next;
+        }
+
+        let encoder = Encoder::from_frequencies(&frequencies).unwrap();
+        let max_bits = encoder
+            .table
+            .code_map()
+            .values()
+            .map(|&(_, bits)| bits)
+            .max()
+            .unwrap();
+
+        assert!(max_bits > 64);
+    }
+
+    #[test]
+    fn table_decoder_handles_codes_longer_than_the_main_table() {
+        // Fibonacci-shaped frequencies force the most unbalanced tree a
+        // Huffman coding can produce, giving codes well past MAIN_TABLE_BITS
+        // with relatively few distinct symbols.
+        let mut content = Vec::new();
+        let (mut a, mut b) = (1usize, 1usize);
+        for symbol in 0u8..20 {
+            content.extend(std::iter::repeat(symbol).take(a));
+            let next = a + b;
+            b = a;
+            a = next;
+        }
+
+        let byte_table = generate_byte_table(&content);
+        let (arena, heap, len) = build_priority_queue(byte_table);
+        let huff_tree = build_huff_tree(arena, heap, len);
+        let huff_table = HuffTable::from_huff_tree(&huff_tree).unwrap();
+        let code_map = huff_table.code_map();
+
+        assert!(huff_table.rows.iter().any(|row| row.bits > MAIN_TABLE_BITS));
+
+        let (packed, valid_bits) = pack(&content, &code_map);
+
+        let table_decoder = TableDecoder::from_huff_table(&huff_table);
+        assert_eq!(table_decoder.decode(&packed, valid_bits), content);
+    }
 }